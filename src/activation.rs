@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use tracing::{debug, warn};
+use zbus::{
+    fdo,
+    names::{OwnedBusName, OwnedWellKnownName},
+    Message,
+};
+
+/// `StartServiceByName` spawned a new process for the name, or found its
+/// activation already in progress.
+pub const DBUS_START_REPLY_SUCCESS: u32 = 1;
+/// `StartServiceByName` found the name already owned; nothing was spawned.
+pub const DBUS_START_REPLY_ALREADY_RUNNING: u32 = 2;
+
+/// A parsed `.service` file, as found in one of the configured activation
+/// directories.
+///
+/// Mirrors the `[D-BUS Service]` section of the reference `dbus-daemon`'s
+/// service file format.
+#[derive(Debug, Clone)]
+pub struct ServiceFile {
+    pub name: OwnedWellKnownName,
+    pub exec: String,
+    pub user: Option<String>,
+    pub systemd_service: Option<String>,
+}
+
+impl ServiceFile {
+    fn parse(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+
+        let mut in_section = false;
+        let mut name = None;
+        let mut exec = None;
+        let mut user = None;
+        let mut systemd_service = None;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_section = line == "[D-BUS Service]";
+                continue;
+            }
+            if !in_section {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                match key.trim() {
+                    "Name" => name = Some(value.trim().to_string()),
+                    "Exec" => exec = Some(value.trim().to_string()),
+                    "User" => user = Some(value.trim().to_string()),
+                    "SystemdService" => systemd_service = Some(value.trim().to_string()),
+                    _ => (),
+                }
+            }
+        }
+
+        let name = name.ok_or_else(|| anyhow::anyhow!("missing `Name=` in {}", path.display()))?;
+        let exec = exec.ok_or_else(|| anyhow::anyhow!("missing `Exec=` in {}", path.display()))?;
+
+        Ok(Self {
+            name: OwnedWellKnownName::try_from(name)?,
+            exec,
+            user,
+            systemd_service,
+        })
+    }
+}
+
+/// Configures `command` to run as `user` once spawned: resolves the
+/// username to a uid/gid via the system's user database, and drops
+/// supplementary groups, gid and uid, in that order, in the child before
+/// `exec`.
+///
+/// Setting `uid`/`gid` alone (e.g. via `CommandExt::uid`/`gid`) would leave
+/// the daemon's own supplementary groups (often root's) attached to the
+/// child; resetting them afterwards would just fail once the uid has
+/// already dropped. So all three are done ourselves, in the right order,
+/// from a single `pre_exec` hook.
+fn apply_user(command: &mut tokio::process::Command, user: &str) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let passwd = nix::unistd::User::from_name(user)?
+        .ok_or_else(|| anyhow::anyhow!("no such user: `{user}`"))?;
+    let name = std::ffi::CString::new(passwd.name.clone())
+        .map_err(|_| anyhow::anyhow!("user name `{user}` contains a NUL byte"))?;
+    let uid = passwd.uid;
+    let gid = passwd.gid;
+
+    // SAFETY: the closure only calls async-signal-safe libc functions
+    // (initgroups, setgid, setuid) between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            nix::unistd::initgroups(&name, gid).map_err(io_error)?;
+            nix::unistd::setgid(gid).map_err(io_error)?;
+            nix::unistd::setuid(uid).map_err(io_error)?;
+
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+fn io_error(errno: nix::errno::Errno) -> std::io::Error {
+    std::io::Error::from_raw_os_error(errno as i32)
+}
+
+/// Loads `.service` files from the configured activation directories and
+/// spawns the associated executable on demand.
+#[derive(Debug, Clone, Default)]
+pub struct ActivationManager {
+    services: Arc<HashMap<OwnedWellKnownName, ServiceFile>>,
+    // Names whose `Exec` process has been spawned but that haven't yet called
+    // `Hello` + `RequestName`. `NameRegistry::route_or_activate` queues
+    // messages destined for these names here, and
+    // `NameRegistry::flush_activation_queue` drains them once the name is
+    // actually claimed.
+    pending: Arc<Mutex<HashMap<OwnedWellKnownName, Vec<Message>>>>,
+}
+
+impl ActivationManager {
+    /// Scans `service_dirs` for `*.service` files, logging and skipping any
+    /// that fail to parse.
+    pub fn new(service_dirs: &[PathBuf]) -> Self {
+        let mut services = HashMap::new();
+
+        for dir in service_dirs {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    debug!("could not read service directory {}: {}", dir.display(), e);
+
+                    continue;
+                }
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("service") {
+                    continue;
+                }
+
+                match ServiceFile::parse(&path) {
+                    Ok(service) => {
+                        services.insert(service.name.clone(), service);
+                    }
+                    Err(e) => warn!("failed to parse service file {}: {}", path.display(), e),
+                }
+            }
+        }
+
+        Self {
+            services: Arc::new(services),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// All well-known names that can be activated, for `ListActivatableNames`.
+    pub fn activatable_names(&self) -> Vec<OwnedBusName> {
+        self.services
+            .keys()
+            .map(|name| OwnedBusName::from(name.clone()))
+            .collect()
+    }
+
+    pub fn is_activatable(&self, name: &OwnedWellKnownName) -> bool {
+        self.services.contains_key(name)
+    }
+
+    /// `true` if `name` has been spawned but hasn't yet claimed its name.
+    pub fn is_pending(&self, name: &OwnedWellKnownName) -> bool {
+        self.pending
+            .lock()
+            .expect("lock poisoned")
+            .contains_key(name)
+    }
+
+    /// Queues `msg` for delivery once the pending activation for `name`
+    /// completes, instead of dropping it. Called from
+    /// `NameRegistry::route_or_activate`.
+    pub fn queue_message(&self, name: &OwnedWellKnownName, msg: Message) {
+        self.pending
+            .lock()
+            .expect("lock poisoned")
+            .entry(name.clone())
+            .or_default()
+            .push(msg);
+    }
+
+    /// Drains and returns the messages queued for `name`. Called from
+    /// `NameRegistry::flush_activation_queue` once the new owner has claimed
+    /// the name, so they can be redelivered to it.
+    pub fn take_queued(&self, name: &OwnedWellKnownName) -> Vec<Message> {
+        self.pending
+            .lock()
+            .expect("lock poisoned")
+            .remove(name)
+            .unwrap_or_default()
+    }
+
+    /// Handles a `StartServiceByName` call, or bus-internal activation of a
+    /// name that turned out to be unowned. `already_owned` should reflect
+    /// whether `name` currently has a primary owner.
+    pub async fn start_service_by_name(
+        &self,
+        name: &OwnedWellKnownName,
+        already_owned: bool,
+    ) -> fdo::Result<u32> {
+        if already_owned {
+            return Ok(DBUS_START_REPLY_ALREADY_RUNNING);
+        }
+
+        // Reserve `name`'s pending slot before doing anything fallible, under
+        // a single lock acquisition, so two concurrent first-touch callers
+        // can't both observe `is_pending() == false` and spawn the `Exec`
+        // process twice.
+        {
+            let mut pending = self.pending.lock().expect("lock poisoned");
+            if pending.contains_key(name) {
+                return Ok(DBUS_START_REPLY_SUCCESS);
+            }
+            pending.insert(name.clone(), Vec::new());
+        }
+
+        let result = self.spawn(name).await;
+        if result.is_err() {
+            // The slot was reserved but nothing ended up running; free it so
+            // a retry isn't wedged behind a spawn that never happened.
+            self.pending.lock().expect("lock poisoned").remove(name);
+        }
+
+        result
+    }
+
+    /// Looks up and spawns `name`'s `Exec` process. The caller must have
+    /// already reserved `name`'s slot in `pending`.
+    async fn spawn(&self, name: &OwnedWellKnownName) -> fdo::Result<u32> {
+        let service = self.services.get(name).ok_or_else(|| {
+            fdo::Error::ServiceUnknown(format!(
+                "The name {name} was not provided by any .service files"
+            ))
+        })?;
+
+        let mut parts = service.exec.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| fdo::Error::SpawnFailed(format!("empty Exec= line for {name}")))?;
+
+        let mut command = tokio::process::Command::new(program);
+        command.args(parts);
+
+        if let Some(user) = &service.user {
+            apply_user(&mut command, user)
+                .map_err(|e| fdo::Error::SpawnFailed(format!("{name}: {e}")))?;
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| fdo::Error::SpawnExecFailed(format!("{}: {e}", service.exec)))?;
+
+        // Reap the child, and if it exits without ever having claimed its
+        // name (crash, or simply never calling `Hello`+`RequestName`), clear
+        // the pending entry so a later `StartServiceByName` retries the
+        // spawn instead of being wedged in "activating" forever.
+        let manager = self.clone();
+        let name = name.clone();
+        tokio::spawn(async move {
+            let status = child.wait().await;
+
+            if manager.is_pending(&name) {
+                let dropped = manager.take_queued(&name).len();
+                warn!(
+                    "activated service for {name} exited ({status:?}) without claiming its \
+                     name, dropping {dropped} queued message(s)"
+                );
+            }
+        });
+
+        Ok(DBUS_START_REPLY_SUCCESS)
+    }
+}
@@ -0,0 +1,686 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, RwLock},
+};
+
+use enumflags2::BitFlags;
+use tracing::warn;
+use zbus::{
+    fdo::{self, ReleaseNameReply, RequestNameFlags, RequestNameReply},
+    names::{BusName, OwnedBusName, OwnedUniqueName, OwnedWellKnownName},
+    Connection, Credentials, Message, MessageBuilder,
+};
+
+use crate::{activation::ActivationManager, peer};
+
+/// A connected peer, as tracked by the registry for signal delivery and
+/// credential lookups.
+#[derive(Debug, Clone)]
+struct PeerEntry {
+    conn: Connection,
+    credentials: Option<Credentials>,
+}
+
+/// One entry in a well-known name's owner queue.
+#[derive(Debug, Clone)]
+struct QueuedOwner {
+    unique_name: OwnedUniqueName,
+    flags: BitFlags<RequestNameFlags>,
+}
+
+/// Keeps track of which unique names currently own, or are queued to own,
+/// each well-known name on the bus, and broadcasts the resulting
+/// ownership-change signals.
+///
+/// Cheaply cloneable: every [`crate::peer::Peer`] holds a handle to the same
+/// underlying table.
+#[derive(Debug, Clone, Default)]
+pub struct NameRegistry {
+    names: Arc<RwLock<HashMap<OwnedWellKnownName, VecDeque<QueuedOwner>>>>,
+    peers: Arc<RwLock<HashMap<OwnedUniqueName, PeerEntry>>>,
+    activation: ActivationManager,
+}
+
+impl NameRegistry {
+    pub fn new(activation: ActivationManager) -> Self {
+        Self {
+            activation,
+            ..Default::default()
+        }
+    }
+
+    /// Looks up the current primary owner of `name`, if any.
+    pub fn lookup(&self, name: impl Into<OwnedWellKnownName>) -> Option<OwnedUniqueName> {
+        self.names
+            .read()
+            .expect("lock poisoned")
+            .get(&name.into())
+            .and_then(|queue| queue.front())
+            .map(|owner| owner.unique_name.clone())
+    }
+
+    /// `true` if `name` currently has a primary owner.
+    pub fn name_has_owner(&self, name: impl Into<OwnedWellKnownName>) -> bool {
+        self.lookup(name).is_some()
+    }
+
+    /// The unique names queued behind (and including) the primary owner of
+    /// `name`, in queue order.
+    pub fn queued_owners(&self, name: impl Into<OwnedWellKnownName>) -> Vec<OwnedUniqueName> {
+        self.names
+            .read()
+            .expect("lock poisoned")
+            .get(&name.into())
+            .map(|queue| {
+                queue
+                    .iter()
+                    .map(|owner| owner.unique_name.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every name known to the bus: the bus's own name, every connected
+    /// unique name, and every currently-owned well-known name.
+    pub fn all_names(&self) -> Vec<OwnedBusName> {
+        let mut names = vec![OwnedBusName::from(
+            OwnedWellKnownName::try_from("org.freedesktop.DBus").expect("valid well-known name"),
+        )];
+
+        names.extend(
+            self.peers
+                .read()
+                .expect("lock poisoned")
+                .keys()
+                .cloned()
+                .map(OwnedBusName::from),
+        );
+        names.extend(
+            self.names
+                .read()
+                .expect("lock poisoned")
+                .iter()
+                .filter(|(_, queue)| !queue.is_empty())
+                .map(|(name, _)| OwnedBusName::from(name.clone())),
+        );
+
+        names
+    }
+
+    /// Registers a newly connected peer so it can receive `NameOwnerChanged`,
+    /// `NameAcquired` and `NameLost` signals, and so its credentials can be
+    /// looked up by `GetConnectionUnixUser` and friends.
+    pub async fn register_peer(
+        &self,
+        unique_name: OwnedUniqueName,
+        conn: Connection,
+        credentials: Option<Credentials>,
+    ) {
+        self.peers
+            .write()
+            .expect("lock poisoned")
+            .insert(unique_name, PeerEntry { conn, credentials });
+    }
+
+    /// Returns the cached credentials of the peer connected as `unique_name`,
+    /// if it's still connected and credentials were available.
+    pub fn credentials(&self, unique_name: &OwnedUniqueName) -> Option<Credentials> {
+        self.peers
+            .read()
+            .expect("lock poisoned")
+            .get(unique_name)
+            .and_then(|entry| entry.credentials.clone())
+    }
+
+    /// Whether `unique_name` refers to a currently connected peer.
+    pub fn is_connected(&self, unique_name: &OwnedUniqueName) -> bool {
+        self.peers
+            .read()
+            .expect("lock poisoned")
+            .contains_key(unique_name)
+    }
+
+    /// Releases every name owned or queued by `unique_name` and forgets the
+    /// peer, called when its connection is torn down.
+    pub async fn unregister_peer(&self, unique_name: &OwnedUniqueName) {
+        self.peers
+            .write()
+            .expect("lock poisoned")
+            .remove(unique_name);
+
+        // Unique names aren't queued like well-known names, so announce this
+        // one directly: it's how other peers (tray/status watchers, polkit-
+        // style agents) notice that a connection vanished.
+        self.emit_owner_changed(unique_name.as_str(), Some(unique_name), None)
+            .await;
+
+        let names: Vec<_> = {
+            let names = self.names.read().expect("lock poisoned");
+            names
+                .iter()
+                .filter(|(_, queue)| queue.iter().any(|owner| &owner.unique_name == unique_name))
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+
+        for name in names {
+            self.drop_owner(&name, unique_name).await;
+        }
+    }
+
+    /// Routes a message sent by one peer towards its destination: forwards
+    /// it to the current owner if `destination` is already owned, or
+    /// triggers (and queues behind) activation if it's an unowned but
+    /// activatable well-known name. Messages with no reachable destination
+    /// are dropped, same as the reference `dbus-daemon` would for a
+    /// `ServiceUnknown` destination.
+    ///
+    /// Called from [`crate::peer::Peer::serve`] for every message read off
+    /// a peer's connection.
+    pub async fn route_message(&self, msg: Message) -> fdo::Result<()> {
+        self.deliver_to_monitors(&msg).await;
+
+        let hdr = msg.header().expect("received message without header");
+
+        match hdr.destination().expect("failed to read DESTINATION field") {
+            Some(BusName::Unique(name)) => {
+                self.forward(&name.to_owned(), msg).await;
+            }
+            Some(BusName::WellKnown(name)) => {
+                let name = name.to_owned();
+
+                match self.lookup(name.clone()) {
+                    Some(owner) => self.forward(&owner, msg).await,
+                    None => self.route_or_activate(&name, msg).await?,
+                }
+            }
+            // No particular destination (e.g. a broadcast signal): nothing
+            // for unicast routing to do.
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Delivers `msg` directly to `unique_name`'s connection, if it's still
+    /// connected.
+    async fn forward(&self, unique_name: &OwnedUniqueName, msg: Message) {
+        let conn = {
+            let peers = self.peers.read().expect("lock poisoned");
+            match peers.get(unique_name) {
+                Some(entry) => entry.conn.clone(),
+                None => return,
+            }
+        };
+
+        if let Err(e) = conn.send_message(msg).await {
+            warn!("failed to forward message to {unique_name}: {}", e);
+        }
+    }
+
+    /// Delivers `msg` to every connected peer that's an active monitor whose
+    /// rules select it, regardless of `msg`'s destination. Monitors are
+    /// eavesdroppers, not normal recipients, so this happens independently
+    /// of (and before) the destination-based forwarding below.
+    async fn deliver_to_monitors(&self, msg: &Message) {
+        let peers: Vec<_> = self
+            .peers
+            .read()
+            .expect("lock poisoned")
+            .values()
+            .map(|entry| entry.conn.clone())
+            .collect();
+
+        for conn in peers {
+            if peer::monitor_interested(&conn, msg).await {
+                if let Err(e) = conn.send_message(msg.clone()).await {
+                    warn!("failed to deliver message to monitor: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Triggers implicit activation of `name` if it's a known activatable
+    /// name, queuing `msg` to be redelivered once the newly-spawned service
+    /// claims it via `Hello`+`RequestName` (see [`Self::request_name`]).
+    /// `msg` is simply dropped if `name` isn't activatable, same as
+    /// [`Self::route_message`]'s other unreachable-destination cases.
+    ///
+    /// Only called from [`Self::route_message`] once it has already
+    /// established that `name` has no current owner.
+    async fn route_or_activate(&self, name: &OwnedWellKnownName, msg: Message) -> fdo::Result<()> {
+        if !self.activation.is_activatable(name) {
+            return Ok(());
+        }
+
+        if !self.activation.is_pending(name) {
+            self.activation.start_service_by_name(name, false).await?;
+        }
+
+        self.activation.queue_message(name, msg);
+
+        Ok(())
+    }
+
+    /// Redelivers any messages queued for `name` while its activation was
+    /// pending, now that `unique_name` has claimed it.
+    async fn flush_activation_queue(&self, name: &OwnedWellKnownName, unique_name: &OwnedUniqueName) {
+        let queued = self.activation.take_queued(name);
+        if queued.is_empty() {
+            return;
+        }
+
+        let conn = {
+            let peers = self.peers.read().expect("lock poisoned");
+            match peers.get(unique_name) {
+                Some(entry) => entry.conn.clone(),
+                None => return,
+            }
+        };
+
+        for msg in queued {
+            if let Err(e) = conn.send_message(msg).await {
+                warn!("failed to redeliver queued message to {unique_name}: {}", e);
+            }
+        }
+    }
+
+    /// Handles a `RequestName` call from `unique_name`.
+    pub async fn request_name(
+        &self,
+        name: impl Into<OwnedWellKnownName>,
+        unique_name: impl Into<OwnedUniqueName>,
+        flags: BitFlags<RequestNameFlags>,
+    ) -> RequestNameReply {
+        let name = name.into();
+        let unique_name = unique_name.into();
+
+        enum Outcome {
+            AlreadyOwner,
+            Exists,
+            Queued,
+            Replaced { previous_owner: OwnedUniqueName },
+            NewOwner,
+        }
+
+        let outcome = {
+            let mut names = self.names.write().expect("lock poisoned");
+            let queue = names.entry(name.clone()).or_default();
+
+            match queue.front() {
+                Some(owner) if owner.unique_name == unique_name => {
+                    queue[0].flags = flags;
+
+                    Outcome::AlreadyOwner
+                }
+                Some(owner) if !owner.flags.contains(RequestNameFlags::AllowReplacement) => {
+                    if flags.contains(RequestNameFlags::DoNotQueue) {
+                        Outcome::Exists
+                    } else if queue.iter().any(|o| o.unique_name == unique_name) {
+                        Outcome::Queued
+                    } else {
+                        queue.push_back(QueuedOwner {
+                            unique_name: unique_name.clone(),
+                            flags,
+                        });
+
+                        Outcome::Queued
+                    }
+                }
+                Some(_) if flags.contains(RequestNameFlags::ReplaceExisting) => {
+                    let previous = queue.pop_front().expect("queue checked non-empty above");
+                    queue.retain(|o| o.unique_name != unique_name);
+
+                    if !previous.flags.contains(RequestNameFlags::DoNotQueue) {
+                        queue.push_back(previous.clone());
+                    }
+                    queue.push_front(QueuedOwner {
+                        unique_name: unique_name.clone(),
+                        flags,
+                    });
+
+                    Outcome::Replaced {
+                        previous_owner: previous.unique_name,
+                    }
+                }
+                Some(_) => {
+                    if flags.contains(RequestNameFlags::DoNotQueue) {
+                        Outcome::Exists
+                    } else if queue.iter().any(|o| o.unique_name == unique_name) {
+                        Outcome::Queued
+                    } else {
+                        queue.push_back(QueuedOwner {
+                            unique_name: unique_name.clone(),
+                            flags,
+                        });
+
+                        Outcome::Queued
+                    }
+                }
+                None => {
+                    queue.push_back(QueuedOwner {
+                        unique_name: unique_name.clone(),
+                        flags,
+                    });
+
+                    Outcome::NewOwner
+                }
+            }
+        };
+
+        match outcome {
+            Outcome::AlreadyOwner => RequestNameReply::AlreadyOwner,
+            Outcome::Exists => RequestNameReply::Exists,
+            Outcome::Queued => RequestNameReply::InQueue,
+            Outcome::NewOwner => {
+                self.emit_owner_changed(name.as_str(), None, Some(&unique_name))
+                    .await;
+                self.emit_directed(&unique_name, "NameAcquired", &name)
+                    .await;
+                self.flush_activation_queue(&name, &unique_name).await;
+
+                RequestNameReply::PrimaryOwner
+            }
+            Outcome::Replaced { previous_owner } => {
+                self.emit_owner_changed(name.as_str(), Some(&previous_owner), Some(&unique_name))
+                    .await;
+                self.emit_directed(&previous_owner, "NameLost", &name).await;
+                self.emit_directed(&unique_name, "NameAcquired", &name)
+                    .await;
+                self.flush_activation_queue(&name, &unique_name).await;
+
+                RequestNameReply::PrimaryOwner
+            }
+        }
+    }
+
+    /// Handles a `ReleaseName` call from `unique_name`.
+    pub async fn release_name(
+        &self,
+        name: impl Into<OwnedWellKnownName>,
+        unique_name: impl Into<OwnedUniqueName>,
+    ) -> ReleaseNameReply {
+        let name = name.into();
+        let unique_name = unique_name.into();
+
+        {
+            let names = self.names.read().expect("lock poisoned");
+            match names.get(&name) {
+                Some(queue) if queue.iter().any(|o| o.unique_name == unique_name) => (),
+                Some(_) => return ReleaseNameReply::NotOwner,
+                None => return ReleaseNameReply::NonExistent,
+            }
+        };
+
+        self.drop_owner(&name, &unique_name).await;
+
+        ReleaseNameReply::Released
+    }
+
+    /// Removes `unique_name` from `name`'s owner queue, promoting the next
+    /// queued owner (if any) and emitting the appropriate signals.
+    async fn drop_owner(&self, name: &OwnedWellKnownName, unique_name: &OwnedUniqueName) {
+        let (was_primary, new_primary) = {
+            let mut names = self.names.write().expect("lock poisoned");
+            let Some(queue) = names.get_mut(name) else {
+                return;
+            };
+
+            let was_primary = queue.front().is_some_and(|o| &o.unique_name == unique_name);
+            queue.retain(|o| &o.unique_name != unique_name);
+            let new_primary = queue.front().map(|o| o.unique_name.clone());
+
+            if queue.is_empty() {
+                names.remove(name);
+            }
+
+            (was_primary, new_primary)
+        };
+
+        if !was_primary {
+            // A queued (non-primary) owner simply drops out; no ownership
+            // change to announce.
+            return;
+        }
+
+        self.emit_owner_changed(name.as_str(), Some(unique_name), new_primary.as_ref())
+            .await;
+        self.emit_directed(unique_name, "NameLost", name).await;
+        if let Some(new_primary) = new_primary {
+            self.emit_directed(&new_primary, "NameAcquired", name).await;
+        }
+    }
+
+    /// Broadcasts `NameOwnerChanged(name, old_owner, new_owner)` to every
+    /// connected peer whose match rules select it.
+    async fn emit_owner_changed(
+        &self,
+        name: &str,
+        old_owner: Option<&OwnedUniqueName>,
+        new_owner: Option<&OwnedUniqueName>,
+    ) {
+        let body = (
+            name,
+            old_owner.map(|o| o.as_str()).unwrap_or_default(),
+            new_owner.map(|o| o.as_str()).unwrap_or_default(),
+        );
+
+        let msg = match MessageBuilder::signal(
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameOwnerChanged",
+        )
+        .and_then(|b| b.sender("org.freedesktop.DBus"))
+        .and_then(|b| b.build(&body))
+        {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!("failed to build NameOwnerChanged signal: {}", e);
+
+                return;
+            }
+        };
+
+        let peers: Vec<_> = self
+            .peers
+            .read()
+            .expect("lock poisoned")
+            .values()
+            .map(|entry| entry.conn.clone())
+            .collect();
+
+        for conn in peers {
+            if peer::interested(&conn, &msg).await {
+                if let Err(e) = conn.send_message(msg.clone()).await {
+                    warn!("failed to deliver NameOwnerChanged: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Sends `NameAcquired`/`NameLost` directly to `unique_name`'s peer.
+    async fn emit_directed(
+        &self,
+        unique_name: &OwnedUniqueName,
+        member: &'static str,
+        name: &OwnedWellKnownName,
+    ) {
+        let conn = {
+            let peers = self.peers.read().expect("lock poisoned");
+            match peers.get(unique_name) {
+                Some(entry) => entry.conn.clone(),
+                None => return,
+            }
+        };
+
+        if let Err(e) = conn
+            .emit_signal(
+                None::<BusName<'_>>,
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus",
+                member,
+                &name.as_str(),
+            )
+            .await
+        {
+            warn!("failed to deliver {member}: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique(n: u32) -> OwnedUniqueName {
+        OwnedUniqueName::try_from(format!(":1.{n}")).unwrap()
+    }
+
+    fn well_known() -> OwnedWellKnownName {
+        OwnedWellKnownName::try_from("com.example.Test").unwrap()
+    }
+
+    #[tokio::test]
+    async fn request_name_grants_an_unowned_name() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+        let owner = unique(1);
+
+        let reply = registry
+            .request_name(name.clone(), owner.clone(), BitFlags::empty())
+            .await;
+
+        assert_eq!(reply, RequestNameReply::PrimaryOwner);
+        assert_eq!(registry.lookup(name), Some(owner));
+    }
+
+    #[tokio::test]
+    async fn request_name_queues_behind_an_existing_owner() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+        let first = unique(1);
+        let second = unique(2);
+
+        registry
+            .request_name(name.clone(), first.clone(), BitFlags::empty())
+            .await;
+        let reply = registry
+            .request_name(name.clone(), second.clone(), BitFlags::empty())
+            .await;
+
+        assert_eq!(reply, RequestNameReply::InQueue);
+        assert_eq!(registry.lookup(name.clone()), Some(first));
+        assert_eq!(registry.queued_owners(name), vec![unique(1), unique(2)]);
+    }
+
+    #[tokio::test]
+    async fn request_name_with_do_not_queue_reports_exists() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+        let first = unique(1);
+        let second = unique(2);
+
+        registry
+            .request_name(name.clone(), first, BitFlags::empty())
+            .await;
+        let reply = registry
+            .request_name(
+                name,
+                second,
+                BitFlags::from(RequestNameFlags::DoNotQueue),
+            )
+            .await;
+
+        assert_eq!(reply, RequestNameReply::Exists);
+    }
+
+    #[tokio::test]
+    async fn request_name_replaces_when_allowed() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+        let first = unique(1);
+        let second = unique(2);
+
+        registry
+            .request_name(
+                name.clone(),
+                first.clone(),
+                BitFlags::from(RequestNameFlags::AllowReplacement),
+            )
+            .await;
+        let reply = registry
+            .request_name(
+                name.clone(),
+                second.clone(),
+                BitFlags::from(RequestNameFlags::ReplaceExisting),
+            )
+            .await;
+
+        assert_eq!(reply, RequestNameReply::PrimaryOwner);
+        assert_eq!(registry.lookup(name.clone()), Some(second));
+        // The replaced owner re-queues behind the new one rather than being
+        // dropped, since it didn't ask for `DoNotQueue`.
+        assert_eq!(registry.queued_owners(name), vec![unique(2), unique(1)]);
+    }
+
+    #[tokio::test]
+    async fn release_name_promotes_the_next_queued_owner() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+        let first = unique(1);
+        let second = unique(2);
+
+        registry
+            .request_name(name.clone(), first.clone(), BitFlags::empty())
+            .await;
+        registry
+            .request_name(name.clone(), second.clone(), BitFlags::empty())
+            .await;
+
+        let reply = registry.release_name(name.clone(), first).await;
+
+        assert_eq!(reply, ReleaseNameReply::Released);
+        assert_eq!(registry.lookup(name), Some(second));
+    }
+
+    #[tokio::test]
+    async fn release_name_of_a_queued_non_primary_owner_leaves_the_owner_unchanged() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+        let first = unique(1);
+        let second = unique(2);
+
+        registry
+            .request_name(name.clone(), first.clone(), BitFlags::empty())
+            .await;
+        registry
+            .request_name(name.clone(), second.clone(), BitFlags::empty())
+            .await;
+
+        registry.release_name(name.clone(), second).await;
+
+        assert_eq!(registry.lookup(name), Some(first));
+    }
+
+    #[tokio::test]
+    async fn release_name_of_an_unowned_name_is_non_existent() {
+        let registry = NameRegistry::new(ActivationManager::default());
+
+        let reply = registry.release_name(well_known(), unique(1)).await;
+
+        assert_eq!(reply, ReleaseNameReply::NonExistent);
+    }
+
+    #[tokio::test]
+    async fn release_name_by_a_non_owner_is_rejected() {
+        let registry = NameRegistry::new(ActivationManager::default());
+        let name = well_known();
+
+        registry
+            .request_name(name.clone(), unique(1), BitFlags::empty())
+            .await;
+        let reply = registry.release_name(name, unique(2)).await;
+
+        assert_eq!(reply, ReleaseNameReply::NotOwner);
+    }
+}
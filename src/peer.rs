@@ -1,22 +1,30 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
 
 use anyhow::Result;
 use enumflags2::BitFlags;
-use tracing::trace;
+use futures_util::StreamExt;
+use tracing::{trace, warn};
 use zbus::{
     dbus_interface,
     fdo::{self, ReleaseNameReply, RequestNameFlags, RequestNameReply},
     names::{BusName, OwnedBusName, OwnedUniqueName, OwnedWellKnownName},
-    AuthMechanism, Connection, ConnectionBuilder, Guid, MessageStream, OwnedMatchRule, Socket,
+    zvariant::{OwnedObjectPath, OwnedValue, Structure, Value},
+    AuthMechanism, Connection, ConnectionBuilder, Credentials, Guid, MessageStream, OwnedMatchRule,
+    Socket,
 };
 
-use crate::name_registry::NameRegistry;
+use crate::{activation::ActivationManager, name_registry::NameRegistry};
 
 /// A peer connection.
 #[derive(Debug)]
 pub struct Peer {
     conn: Connection,
     unique_name: OwnedUniqueName,
+    name_registry: NameRegistry,
+    monitor: MonitorState,
 }
 
 impl Peer {
@@ -25,16 +33,27 @@ impl Peer {
         id: usize,
         socket: Box<dyn Socket + 'static>,
         name_registry: NameRegistry,
+        activation: ActivationManager,
         auth_mechanism: AuthMechanism,
     ) -> Result<Self> {
         let unique_name = OwnedUniqueName::try_from(format!(":busd.{id}")).unwrap();
+        let monitor = MonitorState::default();
 
         let conn = ConnectionBuilder::socket(socket)
             .server(guid)
             .p2p()
             .serve_at(
                 "/org/freedesktop/DBus",
-                DBus::new(unique_name.clone(), name_registry),
+                DBus::new(
+                    unique_name.clone(),
+                    name_registry.clone(),
+                    activation,
+                    monitor.clone(),
+                ),
+            )?
+            .serve_at(
+                "/org/freedesktop/DBus",
+                Monitoring::new(unique_name.clone(), name_registry.clone(), monitor.clone()),
             )?
             .name("org.freedesktop.DBus")?
             .unique_name("org.freedesktop.DBus")?
@@ -43,7 +62,17 @@ impl Peer {
             .await?;
         trace!("created: {:?}", conn);
 
-        Ok(Self { conn, unique_name })
+        let credentials = conn.peer_credentials().await.ok().cloned();
+        name_registry
+            .register_peer(unique_name.clone(), conn.clone(), credentials)
+            .await;
+
+        Ok(Self {
+            conn,
+            unique_name,
+            name_registry,
+            monitor,
+        })
     }
 
     pub fn unique_name(&self) -> &OwnedUniqueName {
@@ -58,68 +87,337 @@ impl Peer {
         MessageStream::from(&self.conn)
     }
 
+    /// Whether this peer has called `BecomeMonitor` and should receive
+    /// eavesdropped copies of bus traffic instead of normal routing.
+    pub fn is_monitor(&self) -> bool {
+        self.monitor.is_active()
+    }
+
+    /// Whether this monitor's rules select `msg`. Only meaningful when
+    /// [`Peer::is_monitor`] is `true`.
+    pub fn monitor_interested(&self, msg: &zbus::Message) -> bool {
+        self.monitor.matches(msg)
+    }
+
+    /// Reads and routes messages off this peer's connection until it
+    /// disconnects. The actual call site for [`NameRegistry::route_message`]
+    /// (and, through it, implicit activation and monitor eavesdropping).
+    pub async fn serve(self) -> Result<()> {
+        let mut stream = self.stream();
+
+        while let Some(msg) = stream.next().await {
+            let msg = msg?;
+
+            if self.is_monitor() {
+                // Monitors don't send ordinary bus traffic; nothing to route.
+                continue;
+            }
+
+            if let Err(e) = self.name_registry.route_message(msg).await {
+                warn!("failed to route message: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
     /// # Panics
     ///
     /// if header, SENDER or DESTINATION is not set.
     pub async fn interested(&self, msg: &zbus::Message) -> bool {
-        let dbus_ref = self
-            .conn
-            .object_server()
-            .interface::<_, DBus>("/org/freedesktop/DBus")
-            .await
-            .expect("DBus interface not found");
-        let dbus = dbus_ref.get().await;
-        let hdr = msg.header().expect("received message without header");
-
-        dbus.match_rules.iter().any(|rule| {
-            // First make use of zbus API
-            match rule.matches(msg) {
-                Ok(false) => return false,
-                Ok(true) => (),
-                Err(e) => {
-                    tracing::warn!("error matching rule: {}", e);
-
-                    return false;
+        if self.is_monitor() {
+            // Monitors are eavesdroppers, not normal recipients; the routing
+            // loop delivers to them separately via `monitor_interested`.
+            return false;
+        }
+
+        interested(&self.conn, msg).await
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        let name_registry = self.name_registry.clone();
+        let unique_name = self.unique_name.clone();
+
+        tokio::spawn(async move {
+            name_registry.unregister_peer(&unique_name).await;
+        });
+    }
+}
+
+/// A match rule together with the routing keys `zbus`'s own `MatchRule`
+/// doesn't understand: `arg0namespace`, `path_namespace` and `eavesdrop`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ExtendedMatchRule {
+    rule: OwnedMatchRule,
+    arg0namespace: Option<String>,
+    path_namespace: Option<OwnedObjectPath>,
+    eavesdrop: bool,
+}
+
+impl ExtendedMatchRule {
+    /// Parses a raw `AddMatch`/`RemoveMatch` rule string, pulling out the
+    /// keys `zbus` doesn't natively support before handing the rest to
+    /// [`OwnedMatchRule`].
+    fn parse(raw: &str) -> fdo::Result<Self> {
+        let mut arg0namespace = None;
+        let mut path_namespace = None;
+        let mut eavesdrop = false;
+        let mut rest = Vec::new();
+
+        for item in split_match_rule(raw) {
+            let item = item.trim();
+            let Some((key, value)) = item.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let value = value.trim_matches('\'');
+
+            match key {
+                "arg0namespace" => arg0namespace = Some(value.to_string()),
+                "path_namespace" => {
+                    path_namespace = Some(
+                        OwnedObjectPath::try_from(value)
+                            .map_err(|e| fdo::Error::MatchRuleInvalid(e.to_string()))?,
+                    );
                 }
+                "eavesdrop" => eavesdrop = value == "true",
+                _ => rest.push(item),
             }
+        }
 
-            // Then match sender and destination involving well-known names, manually.
-            if let Some(sender) = rule.sender().cloned().and_then(|name| match name {
-                BusName::WellKnown(name) => dbus.name_registry.lookup(name).as_deref().cloned(),
-                // Unique name is already taken care of by the zbus API.
-                BusName::Unique(_) => None,
-            }) {
-                if sender
-                    != hdr
-                        .sender()
-                        .expect("SENDER field unset")
-                        .expect("SENDER field unset")
-                        .clone()
-                {
-                    return false;
-                }
+        let rule = OwnedMatchRule::try_from(rest.join(",").as_str())
+            .map_err(|e| fdo::Error::MatchRuleInvalid(e.to_string()))?;
+
+        Ok(Self {
+            rule,
+            arg0namespace,
+            path_namespace,
+            eavesdrop,
+        })
+    }
+}
+
+/// Splits a match rule string on top-level commas, ignoring commas inside
+/// single-quoted values (e.g. `member='Foo,Bar'`).
+fn split_match_rule(raw: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in raw.char_indices() {
+        match c {
+            '\'' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                items.push(&raw[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    items.push(&raw[start..]);
+
+    items
+}
+
+/// The first argument of `msg`'s body, if it's a string.
+fn first_string_arg(msg: &zbus::Message) -> Option<String> {
+    let body: Structure<'_> = msg.body().ok()?;
+
+    match body.fields().first()? {
+        Value::Str(s) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// Whether `conn`'s peer has a match rule selecting `msg`.
+///
+/// Shared between [`Peer::interested`] and [`NameRegistry`]'s broadcasting of
+/// `NameOwnerChanged`, which needs to evaluate the same rules against peers
+/// it doesn't otherwise have a handle to.
+///
+/// # Panics
+///
+/// if header, SENDER or DESTINATION is not set.
+pub(crate) async fn interested(conn: &Connection, msg: &zbus::Message) -> bool {
+    let dbus_ref = conn
+        .object_server()
+        .interface::<_, DBus>("/org/freedesktop/DBus")
+        .await
+        .expect("DBus interface not found");
+    let dbus = dbus_ref.get().await;
+
+    if dbus.monitor.is_active() {
+        // Monitors are eavesdroppers, not normal recipients; they receive
+        // messages through their own rule set (`monitor_interested`)
+        // instead, regardless of which match rules they added before
+        // becoming a monitor.
+        return false;
+    }
+
+    let hdr = msg.header().expect("received message without header");
+
+    // Whether this peer is the message's destination (or the message has no
+    // particular destination, e.g. a broadcast signal). Only `eavesdrop=true`
+    // rules may match messages destined elsewhere.
+    let self_is_destination = match hdr.destination() {
+        Ok(Some(BusName::Unique(name))) => *name == *dbus.unique_name,
+        Ok(Some(BusName::WellKnown(name))) => dbus
+            .name_registry
+            .lookup(name.to_owned())
+            .is_some_and(|owner| owner == dbus.unique_name),
+        Ok(None) | Err(_) => true,
+    };
+
+    dbus.match_rules.iter().any(|rule| {
+        // First make use of zbus API
+        match rule.rule.matches(msg) {
+            Ok(false) => return false,
+            Ok(true) => (),
+            Err(e) => {
+                tracing::warn!("error matching rule: {}", e);
+
+                return false;
             }
+        }
 
-            // The destination.
-            if let Some(destination) = rule.destination() {
-                match hdr
-                    .destination()
-                    .expect("DESTINATION field unset")
-                    .expect("DESTINATION field unset")
+        // Then match sender and destination involving well-known names, manually.
+        if let Some(sender) = rule.rule.sender().cloned().and_then(|name| match name {
+            BusName::WellKnown(name) => dbus.name_registry.lookup(name).as_deref().cloned(),
+            // Unique name is already taken care of by the zbus API.
+            BusName::Unique(_) => None,
+        }) {
+            if sender
+                != hdr
+                    .sender()
+                    .expect("SENDER field unset")
+                    .expect("SENDER field unset")
                     .clone()
-                {
-                    BusName::WellKnown(name) => match dbus.name_registry.lookup(name) {
-                        Some(name) if name == *destination => (),
-                        Some(_) => return false,
-                        None => return false,
-                    },
-                    // Unique name is already taken care of by the zbus API.
-                    BusName::Unique(_) => {}
-                }
+            {
+                return false;
+            }
+        }
+
+        // The destination.
+        if let Some(destination) = rule.rule.destination() {
+            match hdr
+                .destination()
+                .expect("DESTINATION field unset")
+                .expect("DESTINATION field unset")
+                .clone()
+            {
+                BusName::WellKnown(name) => match dbus.name_registry.lookup(name) {
+                    Some(name) if name == *destination => (),
+                    Some(_) => return false,
+                    None => return false,
+                },
+                // Unique name is already taken care of by the zbus API.
+                BusName::Unique(_) => {}
             }
+        }
 
-            true
-        })
+        // `arg0namespace`: prefix-match on the first string argument's dotted namespace.
+        if let Some(namespace) = &rule.arg0namespace {
+            match first_string_arg(msg) {
+                Some(arg0) if &arg0 == namespace || arg0.starts_with(&format!("{namespace}.")) => {}
+                _ => return false,
+            }
+        }
+
+        // `path_namespace`: the message path is the value, or a child of it.
+        if let Some(prefix) = &rule.path_namespace {
+            let path = match hdr.path() {
+                Ok(Some(path)) => path,
+                _ => return false,
+            };
+            let prefix = prefix.as_str();
+
+            // The root namespace matches every path, so don't build a "//"
+            // child-prefix for it; every other prefix gets the trailing `/`
+            // added back for the comparison.
+            let is_child = prefix == "/" || path.as_str().starts_with(&format!("{prefix}/"));
+
+            if path.as_str() != prefix && !is_child {
+                return false;
+            }
+        }
+
+        // Only an `eavesdrop=true` rule may match messages destined elsewhere.
+        if !self_is_destination && !rule.eavesdrop {
+            return false;
+        }
+
+        true
+    })
+}
+
+/// Whether `conn`'s peer is an active monitor whose rules select `msg`.
+///
+/// Mirrors [`interested`], but for the separate eavesdropping path:
+/// `NameRegistry::route_message` calls this for every connected peer so
+/// monitors get a copy of traffic regardless of its destination, instead of
+/// going through the normal match-rule/routing path at all.
+///
+/// # Panics
+///
+/// if the `DBus` interface isn't registered at `/org/freedesktop/DBus`.
+pub(crate) async fn monitor_interested(conn: &Connection, msg: &zbus::Message) -> bool {
+    let dbus_ref = conn
+        .object_server()
+        .interface::<_, DBus>("/org/freedesktop/DBus")
+        .await
+        .expect("DBus interface not found");
+    let dbus = dbus_ref.get().await;
+
+    dbus.monitor.matches(msg)
+}
+
+/// Whether `credentials` identify a peer allowed to eavesdrop on bus traffic
+/// via an `eavesdrop=true` match rule or `BecomeMonitor`. Only root may,
+/// mirroring the reference `dbus-daemon`'s stock system/session policy,
+/// which restricts eavesdropping to privileged users.
+fn privileged_for_eavesdrop(credentials: Option<&Credentials>) -> bool {
+    credentials.and_then(|c| c.unix_user_id()) == Some(0)
+}
+
+/// Shared monitor state for a single peer connection, accessible from both
+/// the `org.freedesktop.DBus` and `org.freedesktop.DBus.Monitoring`
+/// interfaces served at the same path.
+#[derive(Debug, Clone, Default)]
+struct MonitorState {
+    inner: Arc<RwLock<MonitorInner>>,
+}
+
+#[derive(Debug, Default)]
+struct MonitorInner {
+    active: bool,
+    rules: Vec<OwnedMatchRule>,
+}
+
+impl MonitorState {
+    fn become_monitor(&self, rules: Vec<OwnedMatchRule>) {
+        let mut inner = self.inner.write().expect("lock poisoned");
+        inner.active = true;
+        inner.rules = rules;
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.read().expect("lock poisoned").active
+    }
+
+    /// An empty rule set means "match everything", per `BecomeMonitor`.
+    fn matches(&self, msg: &zbus::Message) -> bool {
+        let inner = self.inner.read().expect("lock poisoned");
+        if !inner.active {
+            return false;
+        }
+
+        inner.rules.is_empty()
+            || inner
+                .rules
+                .iter()
+                .any(|rule| matches!(rule.matches(msg), Ok(true)))
     }
 }
 
@@ -128,24 +426,52 @@ struct DBus {
     greeted: bool,
     unique_name: OwnedUniqueName,
     name_registry: NameRegistry,
-    match_rules: HashSet<OwnedMatchRule>,
+    activation: ActivationManager,
+    monitor: MonitorState,
+    match_rules: HashSet<ExtendedMatchRule>,
 }
 
 impl DBus {
-    fn new(unique_name: OwnedUniqueName, name_registry: NameRegistry) -> Self {
+    fn new(
+        unique_name: OwnedUniqueName,
+        name_registry: NameRegistry,
+        activation: ActivationManager,
+        monitor: MonitorState,
+    ) -> Self {
         Self {
             greeted: false,
             unique_name,
             name_registry,
+            activation,
+            monitor,
             match_rules: HashSet::new(),
         }
     }
+
+    /// Resolves `bus_name` to its owning peer's cached credentials.
+    fn connection_credentials(&self, bus_name: OwnedBusName) -> fdo::Result<Credentials> {
+        let unique_name = match bus_name.into_inner() {
+            BusName::WellKnown(name) => self.name_registry.lookup(name).ok_or_else(|| {
+                fdo::Error::NameHasNoOwner("Name is not owned by anyone. Take it!".to_string())
+            })?,
+            BusName::Unique(name) => name.into(),
+        };
+
+        self.name_registry.credentials(&unique_name).ok_or_else(|| {
+            fdo::Error::NameHasNoOwner("Name is not owned by anyone. Take it!".to_string())
+        })
+    }
 }
 
 #[dbus_interface(interface = "org.freedesktop.DBus")]
 impl DBus {
     /// Returns the unique name assigned to the connection.
     async fn hello(&mut self) -> fdo::Result<OwnedUniqueName> {
+        if self.monitor.is_active() {
+            return Err(fdo::Error::Failed(
+                "Monitoring connections must not call `Hello`".to_string(),
+            ));
+        }
         if self.greeted {
             return Err(fdo::Error::Failed(
                 "Can only call `Hello` method once".to_string(),
@@ -157,19 +483,35 @@ impl DBus {
     }
 
     /// Ask the message bus to assign the given name to the method caller.
-    fn request_name(
+    async fn request_name(
         &self,
         name: OwnedWellKnownName,
         flags: BitFlags<RequestNameFlags>,
-    ) -> RequestNameReply {
-        self.name_registry
+    ) -> fdo::Result<RequestNameReply> {
+        if self.monitor.is_active() {
+            return Err(fdo::Error::Failed(
+                "Monitoring connections must not call `RequestName`".to_string(),
+            ));
+        }
+
+        Ok(self
+            .name_registry
             .request_name(name, self.unique_name.clone(), flags)
+            .await)
     }
 
     /// Ask the message bus to release the method caller's claim to the given name.
-    fn release_name(&self, name: OwnedWellKnownName) -> ReleaseNameReply {
-        self.name_registry
+    async fn release_name(&self, name: OwnedWellKnownName) -> fdo::Result<ReleaseNameReply> {
+        if self.monitor.is_active() {
+            return Err(fdo::Error::Failed(
+                "Monitoring connections must not call `ReleaseName`".to_string(),
+            ));
+        }
+
+        Ok(self
+            .name_registry
             .release_name(name.into(), (&*self.unique_name).into())
+            .await)
     }
 
     /// Returns the unique connection name of the primary owner of the name given.
@@ -178,18 +520,100 @@ impl DBus {
             BusName::WellKnown(name) => self.name_registry.lookup(name).ok_or_else(|| {
                 fdo::Error::NameHasNoOwner("Name is not owned by anyone. Take it!".to_string())
             }),
-            // FIXME: Not good enough. We need to check if name is actually owned.
-            BusName::Unique(name) => Ok(name.into()),
+            BusName::Unique(name) => {
+                let name: OwnedUniqueName = name.into();
+                if self.name_registry.is_connected(&name) {
+                    Ok(name)
+                } else {
+                    Err(fdo::Error::NameHasNoOwner(
+                        "Name is not owned by anyone. Take it!".to_string(),
+                    ))
+                }
+            }
         }
     }
 
+    /// Returns all currently-known names on the bus: the bus itself, every
+    /// connected unique name, and every owned well-known name.
+    fn list_names(&self) -> Vec<OwnedBusName> {
+        self.name_registry.all_names()
+    }
+
+    /// Checks if `name` currently has an owner.
+    fn name_has_owner(&self, name: OwnedBusName) -> bool {
+        match name.into_inner() {
+            BusName::WellKnown(name) => self.name_registry.name_has_owner(name),
+            BusName::Unique(name) => self.name_registry.is_connected(&name.into()),
+        }
+    }
+
+    /// Returns the unique names queued up to own `name`, starting with the
+    /// current primary owner.
+    fn list_queued_owners(&self, name: OwnedWellKnownName) -> Vec<OwnedUniqueName> {
+        self.name_registry.queued_owners(name)
+    }
+
+    /// Returns the Unix user ID of the process connected as `bus_name`.
+    fn get_connection_unix_user(&self, bus_name: OwnedBusName) -> fdo::Result<u32> {
+        self.connection_credentials(bus_name)?
+            .unix_user_id()
+            .ok_or_else(|| fdo::Error::Failed("Unable to determine the UID for this name".into()))
+    }
+
+    /// Returns the process ID of the process connected as `bus_name`.
+    fn get_connection_unix_process_id(&self, bus_name: OwnedBusName) -> fdo::Result<u32> {
+        self.connection_credentials(bus_name)?
+            .process_id()
+            .ok_or_else(|| fdo::Error::Failed("Unable to determine the PID for this name".into()))
+    }
+
+    /// Returns as much credential information as the bus has for `bus_name`:
+    /// `UnixUserID`, `ProcessID` and `LinuxSecurityLabel`, whichever are
+    /// available.
+    fn get_connection_credentials(
+        &self,
+        bus_name: OwnedBusName,
+    ) -> fdo::Result<HashMap<String, OwnedValue>> {
+        let credentials = self.connection_credentials(bus_name)?;
+        let mut map = HashMap::new();
+
+        if let Some(uid) = credentials.unix_user_id() {
+            map.insert("UnixUserID".to_string(), OwnedValue::from(uid));
+        }
+        if let Some(pid) = credentials.process_id() {
+            map.insert("ProcessID".to_string(), OwnedValue::from(pid));
+        }
+        if let Some(label) = credentials.linux_security_label() {
+            map.insert(
+                "LinuxSecurityLabel".to_string(),
+                OwnedValue::from(label.to_owned()),
+            );
+        }
+
+        Ok(map)
+    }
+
     /// Adds a match rule to match messages going through the message bus
-    fn add_match(&mut self, rule: OwnedMatchRule) {
+    fn add_match(&mut self, rule: String) -> fdo::Result<()> {
+        let rule = ExtendedMatchRule::parse(&rule)?;
+
+        if rule.eavesdrop
+            && !privileged_for_eavesdrop(self.name_registry.credentials(&self.unique_name).as_ref())
+        {
+            return Err(fdo::Error::AccessDenied(
+                "eavesdrop=true match rules require root".to_string(),
+            ));
+        }
+
         self.match_rules.insert(rule);
+
+        Ok(())
     }
 
     /// Removes the first rule that matches.
-    fn remove_match(&mut self, rule: OwnedMatchRule) -> fdo::Result<()> {
+    fn remove_match(&mut self, rule: String) -> fdo::Result<()> {
+        let rule = ExtendedMatchRule::parse(&rule)?;
+
         if !self.match_rules.remove(&rule) {
             return Err(fdo::Error::MatchRuleNotFound(
                 "No such match rule".to_string(),
@@ -198,4 +622,114 @@ impl DBus {
 
         Ok(())
     }
+
+    /// Tries to launch the executable associated with `name`.
+    async fn start_service_by_name(
+        &self,
+        name: OwnedWellKnownName,
+        _flags: u32,
+    ) -> fdo::Result<u32> {
+        let already_owned = self.name_registry.lookup(name.clone()).is_some();
+
+        self.activation
+            .start_service_by_name(&name, already_owned)
+            .await
+    }
+
+    /// Returns every well-known name that is activatable, whether or not it
+    /// is currently owned by a connected peer.
+    fn list_activatable_names(&self) -> Vec<OwnedBusName> {
+        self.activation.activatable_names()
+    }
+}
+
+/// The `org.freedesktop.DBus.Monitoring` interface, served at the same path
+/// as `org.freedesktop.DBus` so `busctl monitor`/`dbus-monitor`-style tools
+/// can attach to an otherwise ordinary peer connection.
+#[derive(Debug)]
+struct Monitoring {
+    unique_name: OwnedUniqueName,
+    name_registry: NameRegistry,
+    monitor: MonitorState,
+}
+
+impl Monitoring {
+    fn new(unique_name: OwnedUniqueName, name_registry: NameRegistry, monitor: MonitorState) -> Self {
+        Self {
+            unique_name,
+            name_registry,
+            monitor,
+        }
+    }
+}
+
+#[dbus_interface(interface = "org.freedesktop.DBus.Monitoring")]
+impl Monitoring {
+    /// Converts this connection into a monitor: it stops being a normal
+    /// client and instead eavesdrops on every message matching `rules` (or
+    /// every message at all, if `rules` is empty), regardless of sender or
+    /// destination.
+    fn become_monitor(&self, rules: Vec<String>, _flags: u32) -> fdo::Result<()> {
+        if !privileged_for_eavesdrop(self.name_registry.credentials(&self.unique_name).as_ref()) {
+            return Err(fdo::Error::AccessDenied(
+                "BecomeMonitor requires root".to_string(),
+            ));
+        }
+
+        let rules = rules
+            .into_iter()
+            .map(|rule| OwnedMatchRule::try_from(rule.as_str()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| fdo::Error::MatchRuleInvalid(e.to_string()))?;
+
+        self.monitor.become_monitor(rules);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_match_rule_splits_on_top_level_commas() {
+        assert_eq!(
+            split_match_rule("type='signal',sender='com.example.Foo'"),
+            vec!["type='signal'", "sender='com.example.Foo'"],
+        );
+    }
+
+    #[test]
+    fn split_match_rule_ignores_commas_inside_quotes() {
+        assert_eq!(
+            split_match_rule("type='signal',member='Foo,Bar'"),
+            vec!["type='signal'", "member='Foo,Bar'"],
+        );
+    }
+
+    #[test]
+    fn extended_match_rule_recognizes_eavesdrop_after_a_space() {
+        let rule = ExtendedMatchRule::parse("type='signal', eavesdrop=true").unwrap();
+
+        assert!(rule.eavesdrop);
+    }
+
+    #[test]
+    fn extended_match_rule_parses_arg0namespace_and_path_namespace() {
+        let rule = ExtendedMatchRule::parse(
+            "type='signal', arg0namespace='com.example', path_namespace='/com/example'",
+        )
+        .unwrap();
+
+        assert_eq!(rule.arg0namespace.as_deref(), Some("com.example"));
+        assert_eq!(rule.path_namespace.as_ref().unwrap().as_str(), "/com/example");
+    }
+
+    #[test]
+    fn extended_match_rule_rejects_an_invalid_path_namespace() {
+        let err = ExtendedMatchRule::parse("path_namespace='not-absolute'").unwrap_err();
+
+        assert!(matches!(err, fdo::Error::MatchRuleInvalid(_)));
+    }
 }